@@ -0,0 +1,342 @@
+//! Address space abstraction: a page table plus the [`MapArea`]s describing
+//! how its virtual pages are backed
+
+use super::{frame_alloc, FrameTracker, PageTable, PageTableEntry, PTEFlags};
+use super::{PhysPageNum, StepByOne, VirtAddr, VirtPageNum};
+use crate::config::{MEMORY_END, PAGE_SIZE, TRAMPOLINE, TRAP_CONTEXT};
+use crate::sync::UPSafeCell;
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use bitflags::*;
+use lazy_static::*;
+
+bitflags! {
+    /// Permission bits a [`MapArea`] grants through its PTEs, independent of
+    /// the hardware [`PTEFlags`]
+    pub struct MapPermission: u8 {
+        /// readable
+        const R = 1 << 1;
+        /// writable
+        const W = 1 << 2;
+        /// executable
+        const X = 1 << 3;
+        /// accessible from U-mode
+        const U = 1 << 4;
+    }
+}
+
+/// Whether a [`MapArea`]'s pages are backed by dynamically allocated frames
+/// or identity-mapped straight onto physical memory
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum MapType {
+    /// `vpn == ppn`, used for kernel text/data/bss that's already contiguous
+    Identical,
+    /// each vpn gets its own freshly allocated frame
+    Framed,
+}
+
+/// A contiguous run of virtual pages, `[vpn_start, vpn_end)`, mapped with the
+/// same [`MapType`] and [`MapPermission`]
+pub struct MapArea {
+    vpn_start: VirtPageNum,
+    vpn_end: VirtPageNum,
+    data_frames: BTreeMap<VirtPageNum, FrameTracker>,
+    map_type: MapType,
+    map_perm: MapPermission,
+}
+
+impl MapArea {
+    /// Describe a fresh, not-yet-mapped area over `[start_va, end_va)`
+    pub fn new(start_va: VirtAddr, end_va: VirtAddr, map_type: MapType, map_perm: MapPermission) -> Self {
+        Self {
+            vpn_start: start_va.floor(),
+            vpn_end: end_va.ceil(),
+            data_frames: BTreeMap::new(),
+            map_type,
+            map_perm,
+        }
+    }
+
+    fn map_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
+        let ppn = match self.map_type {
+            MapType::Identical => PhysPageNum(vpn.0),
+            MapType::Framed => {
+                let frame = frame_alloc().unwrap();
+                let ppn = frame.ppn;
+                self.data_frames.insert(vpn, frame);
+                ppn
+            }
+        };
+        let pte_flags = PTEFlags::from_bits(self.map_perm.bits()).unwrap();
+        page_table.map(vpn, ppn, pte_flags);
+    }
+
+    /// Map every page currently in `[vpn_start, vpn_end)` into `page_table`
+    pub fn map(&mut self, page_table: &mut PageTable) {
+        let mut vpn = self.vpn_start;
+        while vpn < self.vpn_end {
+            self.map_one(page_table, vpn);
+            vpn.step();
+        }
+    }
+
+    /// Unmap `[start, end)` (a subset of this area's range), dropping backing
+    /// frames; caller narrows/splits the area's own range afterwards
+    fn unmap_range(&mut self, page_table: &mut PageTable, start: VirtPageNum, end: VirtPageNum) {
+        let mut vpn = start;
+        while vpn < end {
+            if self.map_type == MapType::Framed {
+                self.data_frames.remove(&vpn);
+            }
+            page_table.unmap(vpn);
+            vpn.step();
+        }
+    }
+
+    /// Unmap the whole area
+    pub fn unmap(&mut self, page_table: &mut PageTable) {
+        let (start, end) = (self.vpn_start, self.vpn_end);
+        self.unmap_range(page_table, start, end);
+    }
+
+    /// Trim this area down to `[new_start, new_end)`, which must be a subset
+    /// of its current range; unmaps whatever fell outside the new bounds
+    fn shrink_to(&mut self, page_table: &mut PageTable, new_start: VirtPageNum, new_end: VirtPageNum) {
+        if new_start > self.vpn_start {
+            self.unmap_range(page_table, self.vpn_start, new_start);
+        }
+        if new_end < self.vpn_end {
+            self.unmap_range(page_table, new_end, self.vpn_end);
+        }
+        self.vpn_start = new_start;
+        self.vpn_end = new_end;
+    }
+
+    /// Unmap `[start, end)` from the middle of this area, shrinking it to the
+    /// head and returning a new [`MapArea`] for the surviving tail
+    fn split_off(&mut self, page_table: &mut PageTable, start: VirtPageNum, end: VirtPageNum) -> MapArea {
+        let (tail_start, tail_end) = (end, self.vpn_end);
+        let mut tail_frames = BTreeMap::new();
+        if self.map_type == MapType::Framed {
+            let moved: Vec<VirtPageNum> = self
+                .data_frames
+                .range(tail_start..)
+                .map(|(vpn, _)| *vpn)
+                .collect();
+            for vpn in moved {
+                if let Some(frame) = self.data_frames.remove(&vpn) {
+                    tail_frames.insert(vpn, frame);
+                }
+            }
+        }
+        self.unmap_range(page_table, start, end);
+        self.vpn_end = start;
+        MapArea {
+            vpn_start: tail_start,
+            vpn_end: tail_end,
+            data_frames: tail_frames,
+            map_type: self.map_type,
+            map_perm: self.map_perm,
+        }
+    }
+}
+
+/// A task's (or the kernel's) address space: one page table plus the
+/// [`MapArea`]s that own its mapped pages
+pub struct MemorySet {
+    page_table: PageTable,
+    areas: Vec<MapArea>,
+}
+
+impl MemorySet {
+    /// A fresh address space with no mapped areas
+    pub fn new_bare() -> Self {
+        Self {
+            page_table: PageTable::new(),
+            areas: Vec::new(),
+        }
+    }
+
+    /// Token (satp value) identifying this address space's page table
+    pub fn token(&self) -> usize {
+        self.page_table.token()
+    }
+
+    /// Look up the PTE backing `vpn`, if any
+    pub fn translate(&self, vpn: VirtPageNum) -> Option<PageTableEntry> {
+        self.page_table.translate(vpn)
+    }
+
+    fn push(&mut self, mut area: MapArea) {
+        area.map(&mut self.page_table);
+        self.areas.push(area);
+    }
+
+    /// Map a fresh, frame-backed area over `[start_va, end_va)`
+    pub fn insert_framed_area(&mut self, start_va: VirtAddr, end_va: VirtAddr, perm: MapPermission) {
+        self.push(MapArea::new(start_va, end_va, MapType::Framed, perm));
+    }
+
+    fn range_is_mapped(&self, start_vpn: VirtPageNum, end_vpn: VirtPageNum) -> bool {
+        let mut vpn = start_vpn;
+        while vpn < end_vpn {
+            if !self.translate(vpn).map_or(false, |pte| pte.is_valid()) {
+                return false;
+            }
+            vpn.step();
+        }
+        true
+    }
+
+    /// Unmap `[start_va, end_va)`, trimming/splitting whichever [`MapArea`]s
+    /// it overlaps. Returns `0` on success, or `-1` if any page in the range
+    /// was never mapped.
+    pub fn remove_area(&mut self, start_va: VirtAddr, end_va: VirtAddr) -> isize {
+        let start_vpn = start_va.floor();
+        let end_vpn = end_va.ceil();
+        if !self.range_is_mapped(start_vpn, end_vpn) {
+            return -1;
+        }
+
+        let mut idx = 0;
+        while idx < self.areas.len() {
+            let (area_start, area_end) = (self.areas[idx].vpn_start, self.areas[idx].vpn_end);
+            if area_end <= start_vpn || end_vpn <= area_start {
+                // no overlap with the removed range
+                idx += 1;
+                continue;
+            }
+
+            if start_vpn <= area_start && area_end <= end_vpn {
+                // fully covered: unmap it and drop it; the next area slides
+                // into this slot, so don't advance idx
+                let mut area = self.areas.remove(idx);
+                area.unmap(&mut self.page_table);
+                continue;
+            }
+
+            if start_vpn <= area_start {
+                // overlaps the low edge: keep the tail [end_vpn, area_end)
+                self.areas[idx].shrink_to(&mut self.page_table, end_vpn, area_end);
+            } else if area_end <= end_vpn {
+                // overlaps the high edge: keep the head [area_start, start_vpn)
+                self.areas[idx].shrink_to(&mut self.page_table, area_start, start_vpn);
+            } else {
+                // the removed range sits entirely inside this area: split it
+                let tail = self.areas[idx].split_off(&mut self.page_table, start_vpn, end_vpn);
+                self.areas.insert(idx + 1, tail);
+            }
+            idx += 1;
+        }
+        0
+    }
+
+    /// Drop the area whose range starts at `start_vpn`, unmapping it. Used
+    /// to tear down a single area allocated by its start address (e.g. a
+    /// kernel stack), where the caller already knows the exact boundary.
+    pub fn remove_area_with_start_vpn(&mut self, start_vpn: VirtPageNum) {
+        if let Some(idx) = self.areas.iter().position(|area| area.vpn_start == start_vpn) {
+            let mut area = self.areas.remove(idx);
+            area.unmap(&mut self.page_table);
+        }
+    }
+
+    /// Build an address space from an ELF image: one identity-free, framed
+    /// area per loadable segment, plus the trap context and trampoline
+    pub fn from_elf(elf_data: &[u8]) -> (Self, usize, usize) {
+        let mut memory_set = Self::new_bare();
+        memory_set.map_trampoline();
+        let elf = xmas_elf::ElfFile::new(elf_data).unwrap();
+        let elf_header = elf.header;
+        let ph_count = elf_header.pt2.ph_count();
+        let mut max_end_vpn = VirtPageNum(0);
+        for i in 0..ph_count {
+            let ph = elf.program_header(i).unwrap();
+            if ph.get_type().unwrap() == xmas_elf::program::Type::Load {
+                let start_va: VirtAddr = (ph.virtual_addr() as usize).into();
+                let end_va: VirtAddr = ((ph.virtual_addr() + ph.mem_size()) as usize).into();
+                let mut map_perm = MapPermission::U;
+                let ph_flags = ph.flags();
+                if ph_flags.is_read() {
+                    map_perm.insert(MapPermission::R);
+                }
+                if ph_flags.is_write() {
+                    map_perm.insert(MapPermission::W);
+                }
+                if ph_flags.is_execute() {
+                    map_perm.insert(MapPermission::X);
+                }
+                let map_area = MapArea::new(start_va, end_va, MapType::Framed, map_perm);
+                max_end_vpn = map_area.vpn_end;
+                let mut vpn = map_area.vpn_start;
+                memory_set.push(map_area);
+                // copy the segment's file contents into its freshly mapped frames
+                let data = &elf.input[ph.offset() as usize..(ph.offset() + ph.file_size()) as usize];
+                for chunk in data.chunks(PAGE_SIZE) {
+                    let dst = memory_set.translate(vpn).unwrap().ppn().get_bytes_array();
+                    dst[..chunk.len()].copy_from_slice(chunk);
+                    vpn.step();
+                }
+            }
+        }
+        let max_end_va: VirtAddr = max_end_vpn.into();
+        let user_stack_bottom: usize = usize::from(max_end_va) + PAGE_SIZE;
+        let user_stack_top = user_stack_bottom + crate::config::USER_STACK_SIZE;
+        memory_set.insert_framed_area(
+            user_stack_bottom.into(),
+            user_stack_top.into(),
+            MapPermission::R | MapPermission::W | MapPermission::U,
+        );
+        memory_set.insert_framed_area(
+            TRAP_CONTEXT.into(),
+            TRAMPOLINE.into(),
+            MapPermission::R | MapPermission::W,
+        );
+        (
+            memory_set,
+            user_stack_top,
+            elf.header.pt2.entry_point() as usize,
+        )
+    }
+
+    /// Clone another task's address space into a fresh one, copying every
+    /// framed page's contents byte-for-byte
+    pub fn from_existed_user(user_space: &Self) -> Self {
+        let mut memory_set = Self::new_bare();
+        memory_set.map_trampoline();
+        for area in user_space.areas.iter() {
+            let new_area = MapArea::new(area.vpn_start.into(), area.vpn_end.into(), area.map_type, area.map_perm);
+            memory_set.push(new_area);
+            let mut vpn = area.vpn_start;
+            while vpn < area.vpn_end {
+                let src_ppn = user_space.translate(vpn).unwrap().ppn();
+                let dst_ppn = memory_set.translate(vpn).unwrap().ppn();
+                dst_ppn.get_bytes_array().copy_from_slice(src_ppn.get_bytes_array());
+                vpn.step();
+            }
+        }
+        memory_set
+    }
+
+    fn map_trampoline(&mut self) {
+        self.page_table.map(
+            VirtAddr::from(TRAMPOLINE).into(),
+            PhysPageNum((MEMORY_END - PAGE_SIZE) / PAGE_SIZE),
+            PTEFlags::R | PTEFlags::X,
+        );
+    }
+}
+
+lazy_static! {
+    /// The kernel's own address space, shared by every task
+    pub static ref KERNEL_SPACE: Arc<UPSafeCell<MemorySet>> =
+        Arc::new(unsafe { UPSafeCell::new(MemorySet::new_bare()) });
+}
+
+/// Whether `vpn` is currently mapped in the address space identified by `token`
+pub fn is_map_vpn(token: usize, vpn: VirtPageNum) -> bool {
+    PageTable::from_token(token)
+        .translate(vpn)
+        .map_or(false, |pte| pte.is_valid())
+}
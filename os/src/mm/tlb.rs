@@ -0,0 +1,25 @@
+//! TLB maintenance after a task's page table changes underneath it
+
+use super::{StepByOne, VirtAddr, VirtPageNum};
+
+/// Above this many pages, a full `sfence.vma` is cheaper than one per page
+const MAX_TARGETED_PAGES: usize = 64;
+
+/// Invalidate stale TLB entries for `[start, end)` after its mapping changed
+pub fn flush_tlb_range(start: VirtAddr, end: VirtPageNum) {
+    let start_vpn: VirtPageNum = start.floor();
+    if end.0.saturating_sub(start_vpn.0) > MAX_TARGETED_PAGES {
+        unsafe {
+            core::arch::asm!("sfence.vma");
+        }
+        return;
+    }
+    let mut vpn = start_vpn;
+    while vpn < end {
+        let vaddr: VirtAddr = vpn.into();
+        unsafe {
+            core::arch::asm!("sfence.vma {}", in(reg) vaddr.0);
+        }
+        vpn.step();
+    }
+}
@@ -22,6 +22,14 @@ const SYSCALL_YIELD: usize = 124;
 const SYSCALL_GET_TIME: usize = 169;
 /// taskinfo syscall
 const SYSCALL_TASK_INFO: usize = 410;
+/// getpid syscall
+const SYSCALL_GETPID: usize = 172;
+/// fork syscall
+const SYSCALL_FORK: usize = 220;
+/// exec syscall
+const SYSCALL_EXEC: usize = 221;
+/// waitpid syscall
+const SYSCALL_WAITPID: usize = 260;
 /// SYSCALL-id 和 tasks桶id的映射    这个是通过和群友交流才意识到的
 pub const SYSCALL_MAP: [usize; CH2_SYSCALL_NUM] = [
     SYSCALL_WRITE,
@@ -44,6 +52,10 @@ pub fn syscall(syscall_id: usize, args: [usize; 3]) -> isize {
         SYSCALL_YIELD => sys_yield(),
         SYSCALL_GET_TIME => sys_get_time(args[0] as *mut TimeVal, args[1]),
         SYSCALL_TASK_INFO => sys_task_info(args[0] as *mut TaskInfo),
+        SYSCALL_GETPID => sys_getpid(),
+        SYSCALL_FORK => sys_fork(),
+        SYSCALL_EXEC => sys_exec(args[0] as *const u8),
+        SYSCALL_WAITPID => sys_waitpid(args[0] as isize, args[1] as *mut i32),
         _ => panic!("Unsupported syscall_id: {}", syscall_id),
     }
 }
@@ -1,14 +1,16 @@
 //! Process management syscalls
 use crate::{
     config::{MAX_SYSCALL_NUM, PAGE_SIZE},
+    loader::get_app_data_by_name,
     task::{
-        change_program_brk, exit_current_and_run_next, suspend_current_and_run_next, TaskStatus,
-        curr_translate_refmut, get_current_running_time, get_current_syscalls_cnt, is_map_vpn_current,
-        remove_mem, add_maparea,
+        add_task, change_program_brk, current_task, exit_current_and_run_next,
+        suspend_current_and_run_next, TaskStatus, current_user_token, get_current_running_time,
+        get_current_syscalls_cnt, is_map_vpn_current, remove_mem, add_maparea,
     },
-    timer::{get_time_us, get_time_ms,},
-    mm::{VirtAddr, VirtPageNum, StepByOne},
+    timer::get_time_us,
+    mm::{VirtAddr, VirtPageNum, StepByOne, translated_byte_buffer, translated_str},
 };
+use alloc::sync::Arc;
 use super::{CH4_SYSCALL_CNT, TONG_MAP_SYSCALL};
 
 #[repr(C)]
@@ -29,10 +31,26 @@ pub struct TaskInfo {
     time: usize,
 }
 
+/// 把一个结构体按字节拷贝到用户态指针指向的内存中
+/// 由于用户态的这段内存可能跨越两个物理页帧，这里借助
+/// [`translated_byte_buffer`] 逐页拷贝，而不是直接取一个跨页的 `&mut T`
+fn write_user_struct<T>(ptr: *mut T, data: &T) {
+    let token = current_user_token();
+    let src = unsafe {
+        core::slice::from_raw_parts(data as *const T as *const u8, core::mem::size_of::<T>())
+    };
+    let mut offset = 0;
+    for dst in translated_byte_buffer(token, ptr as *const u8, core::mem::size_of::<T>()) {
+        let len = dst.len();
+        dst.copy_from_slice(&src[offset..offset + len]);
+        offset += len;
+    }
+}
+
 /// task exits and submit an exit code
-pub fn sys_exit(_exit_code: i32) -> ! {
+pub fn sys_exit(exit_code: i32) -> ! {
     trace!("kernel: sys_exit");
-    exit_current_and_run_next();
+    exit_current_and_run_next(exit_code);
     panic!("Unreachable in sys_exit!");
 }
 
@@ -43,33 +61,34 @@ pub fn sys_yield() -> isize {
     0
 }
 
-/// YOUR JOB: get time with second and microsecond
-/// HINT: You might reimplement it with virtual memory management.
-/// HINT: What if [`TimeVal`] is splitted by two pages ? 
-/// 这里并没有解决这个问题，因为get_refmut并没有解决物理地址分页的情况
+/// get time with second and microsecond
 pub fn sys_get_time(ts: *mut TimeVal, _tz: usize) -> isize {
     trace!("kernel: sys_get_time");
 
     let us = get_time_us();
-    let ts_ref = curr_translate_refmut(ts);
-    ts_ref.sec = us / 1_000_000;
-    ts_ref.usec = us % 1_000_000;
+    let time_val = TimeVal {
+        sec: us / 1_000_000,
+        usec: us % 1_000_000,
+    };
+    write_user_struct(ts, &time_val);
     0
 }
 
-/// YOUR JOB: Finish sys_task_info to pass testcases
-/// HINT: You might reimplement it with virtual memory management.
-/// HINT: What if [`TaskInfo`] is splitted by two pages ?
+/// get the current task's status, syscall counts and running time
 pub fn sys_task_info(ti: *mut TaskInfo) -> isize {
     trace!("kernel: sys_task_info");
-    
-    let ti_ref = curr_translate_refmut(ti);
-    ti_ref.time = get_current_running_time(get_time_ms());
-    ti_ref.status = TaskStatus::Running;
+
+    let mut syscall_times = [0u32; MAX_SYSCALL_NUM];
     let tong_syscalls_cnt = get_current_syscalls_cnt();
     for id in 0..CH4_SYSCALL_CNT {
-        ti_ref.syscall_times[TONG_MAP_SYSCALL[id]] = tong_syscalls_cnt[id] as u32;
+        syscall_times[TONG_MAP_SYSCALL[id]] = tong_syscalls_cnt[id] as u32;
     }
+    let task_info = TaskInfo {
+        status: TaskStatus::Running,
+        syscall_times,
+        time: get_current_running_time(),
+    };
+    write_user_struct(ti, &task_info);
     0
 }
 
@@ -137,3 +156,69 @@ pub fn sys_sbrk(size: i32) -> isize {
         -1
     }
 }
+
+/// get the pid of the current task
+pub fn sys_getpid() -> isize {
+    trace!("kernel: sys_getpid");
+    current_task().unwrap().getpid() as isize
+}
+
+/// clone the current task into a child that returns 0 from this syscall;
+/// the parent gets the child's pid back
+pub fn sys_fork() -> isize {
+    trace!("kernel: sys_fork");
+    let current_task = current_task().unwrap();
+    let new_task = current_task.fork();
+    let new_pid = new_task.getpid();
+    // modify trap context of new_task, because it returns immediately after switching
+    let trap_cx = new_task.inner_exclusive_access().get_trap_cx();
+    // we do not have to move to next instruction since we have done it before
+    // for child process, fork returns 0
+    trap_cx.x[10] = 0;
+    add_task(new_task);
+    new_pid as isize
+}
+
+/// replace the current task's address space with the named ELF image
+pub fn sys_exec(path: *const u8) -> isize {
+    trace!("kernel: sys_exec");
+    let token = current_user_token();
+    let path = translated_str(token, path);
+    if let Some(data) = get_app_data_by_name(path.as_str()) {
+        let task = current_task().unwrap();
+        task.exec(data);
+        0
+    } else {
+        -1
+    }
+}
+
+/// reap a zombie child matching `pid` (or any child if `pid == -1`), writing
+/// its exit code through `exit_code_ptr` and returning its pid; returns -1 if
+/// no such child exists, or -2 if it exists but hasn't exited yet
+pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
+    trace!("kernel: sys_waitpid");
+    let task = current_task().unwrap();
+
+    let mut inner = task.inner_exclusive_access();
+    if !inner
+        .children
+        .iter()
+        .any(|child| pid == -1 || pid as usize == child.getpid())
+    {
+        return -1;
+    }
+    let pair = inner.children.iter().enumerate().find(|(_, child)| {
+        child.inner_exclusive_access().is_zombie() && (pid == -1 || pid as usize == child.getpid())
+    });
+    if let Some((idx, _)) = pair {
+        let child = inner.children.remove(idx);
+        assert_eq!(Arc::strong_count(&child), 1);
+        let found_pid = child.getpid();
+        let exit_code = child.inner_exclusive_access().exit_code;
+        write_user_struct(exit_code_ptr, &exit_code);
+        found_pid as isize
+    } else {
+        -2
+    }
+}
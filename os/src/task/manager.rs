@@ -0,0 +1,192 @@
+//! Pluggable task scheduling policy
+
+use super::TaskControlBlock;
+use crate::sync::UPSafeCell;
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use lazy_static::*;
+
+/// A scheduling policy over the set of ready tasks
+pub trait Scheduler {
+    /// Add a newly-ready task to the policy's pool
+    fn insert(&mut self, task: Arc<TaskControlBlock>);
+    /// Remove and return the task that should run next
+    fn pop(&mut self) -> Option<Arc<TaskControlBlock>>;
+}
+
+/// First-in-first-out scheduler: tasks run in the order they became ready
+pub struct FifoScheduler {
+    ready_queue: VecDeque<Arc<TaskControlBlock>>,
+}
+
+impl FifoScheduler {
+    /// Create an empty FIFO ready queue
+    pub fn new() -> Self {
+        Self {
+            ready_queue: VecDeque::new(),
+        }
+    }
+}
+
+impl Scheduler for FifoScheduler {
+    fn insert(&mut self, task: Arc<TaskControlBlock>) {
+        self.ready_queue.push_back(task);
+    }
+
+    fn pop(&mut self) -> Option<Arc<TaskControlBlock>> {
+        self.ready_queue.pop_front()
+    }
+}
+
+/// Minimum allowed `prio_level`, keeping a single pass under `BIG_STRIDER / 2`
+/// so the spread between any two live strides stays within the slack
+/// [`stride_less`] needs to order values across wraparound.
+pub const MIN_PRIO_LEVEL: u8 = 2;
+
+/// Order two `u8` strides so comparisons stay correct across wraparound
+fn stride_less(a: u8, b: u8) -> bool {
+    (a.wrapping_sub(b) as i8) < 0
+}
+
+/// Stride scheduler: always hands out the ready task with the smallest stride
+pub struct StrideScheduler {
+    ready_queue: Vec<Arc<TaskControlBlock>>,
+}
+
+impl StrideScheduler {
+    /// Create an empty stride ready pool
+    pub fn new() -> Self {
+        Self {
+            ready_queue: Vec::new(),
+        }
+    }
+
+    /// Index of the task with the smallest `stride`, comparing wraparound-safely
+    fn min_stride_index(&self) -> Option<usize> {
+        self.ready_queue
+            .iter()
+            .map(|task| task.inner_exclusive_access().stride)
+            .enumerate()
+            .reduce(|min, cur| if stride_less(cur.1, min.1) { cur } else { min })
+            .map(|(idx, _)| idx)
+    }
+}
+
+impl Scheduler for StrideScheduler {
+    fn insert(&mut self, task: Arc<TaskControlBlock>) {
+        debug_assert!(
+            task.inner_exclusive_access().prio_level >= MIN_PRIO_LEVEL,
+            "prio_level must be >= {} for stride comparisons to stay wraparound-safe",
+            MIN_PRIO_LEVEL
+        );
+        self.ready_queue.push(task);
+    }
+
+    fn pop(&mut self) -> Option<Arc<TaskControlBlock>> {
+        self.min_stride_index().map(|idx| self.ready_queue.remove(idx))
+    }
+}
+
+/// The ready-queue owner: holds whichever [`Scheduler`] policy is compiled in
+pub struct TaskManager {
+    scheduler: Box<dyn Scheduler + Send + Sync>,
+}
+
+impl TaskManager {
+    /// Build a task manager backed by the default scheduling policy.
+    ///
+    /// Stride scheduling is the default; building with `--features
+    /// fifo_sched` swaps in [`FifoScheduler`] instead, so the policy is
+    /// chosen at compile time rather than hardcoded here.
+    pub fn new() -> Self {
+        Self {
+            scheduler: Self::default_scheduler(),
+        }
+    }
+
+    #[cfg(not(feature = "fifo_sched"))]
+    fn default_scheduler() -> Box<dyn Scheduler + Send + Sync> {
+        Box::new(StrideScheduler::new())
+    }
+
+    #[cfg(feature = "fifo_sched")]
+    fn default_scheduler() -> Box<dyn Scheduler + Send + Sync> {
+        Box::new(FifoScheduler::new())
+    }
+
+    /// Mark a task ready and hand it to the scheduling policy
+    pub fn add(&mut self, task: Arc<TaskControlBlock>) {
+        self.scheduler.insert(task);
+    }
+
+    /// Ask the scheduling policy for the next task to run
+    pub fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
+        self.scheduler.pop()
+    }
+}
+
+lazy_static! {
+    /// The global ready-queue / scheduler, guarded like every other shared kernel structure
+    pub static ref TASK_MANAGER: UPSafeCell<TaskManager> = unsafe { UPSafeCell::new(TaskManager::new()) };
+}
+
+/// Push a task onto the ready queue
+pub fn add_task(task: Arc<TaskControlBlock>) {
+    TASK_MANAGER.exclusive_access().add(task);
+}
+
+/// Pop the next task to run off the ready queue, per the active scheduling policy
+pub fn fetch_task() -> Option<Arc<TaskControlBlock>> {
+    TASK_MANAGER.exclusive_access().fetch()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::stride_less;
+
+    /// mirrors `BIG_STRIDER` in `processor.rs`
+    const BIG_STRIDER: u8 = 255;
+
+    #[test]
+    fn stride_less_orders_normally() {
+        assert!(stride_less(1, 2));
+        assert!(!stride_less(2, 1));
+        assert!(!stride_less(5, 5));
+    }
+
+    #[test]
+    fn stride_less_orders_across_wraparound() {
+        // 10 is "ahead" of 250 once it has wrapped past 255 back to a small value
+        assert!(stride_less(250, 10));
+        assert!(!stride_less(10, 250));
+    }
+
+    /// Simulates `StrideScheduler::pop` picking the smallest (wraparound-safe)
+    /// stride repeatedly, for tasks with different `prio_level`s, well past
+    /// the point where `u8` strides wrap around. Run counts should still
+    /// land in proportion to `prio_level`.
+    #[test]
+    fn run_counts_stay_proportional_to_priority_past_wraparound() {
+        let prio_levels: [u8; 2] = [2, 4];
+        let mut strides: [u8; 2] = [0, 0];
+        let mut run_counts: [u32; 2] = [0, 0];
+
+        // enough rounds to wrap a u8 stride (up to BIG_STRIDER/2 per round) several times over
+        for _ in 0..2000 {
+            let picked = if stride_less(strides[1], strides[0]) { 1 } else { 0 };
+            run_counts[picked] += 1;
+            strides[picked] = strides[picked].wrapping_add(BIG_STRIDER / prio_levels[picked]);
+        }
+
+        let ratio = run_counts[1] as f64 / run_counts[0] as f64;
+        let expected_ratio = prio_levels[1] as f64 / prio_levels[0] as f64;
+        assert!(
+            (ratio - expected_ratio).abs() < 0.1,
+            "run count ratio {} should track priority ratio {}",
+            ratio,
+            expected_ratio
+        );
+    }
+}
@@ -0,0 +1,80 @@
+//! Task management implementation
+
+mod context;
+mod manager;
+mod pid;
+mod processor;
+mod switch;
+#[allow(clippy::module_inception)]
+mod task;
+
+use alloc::sync::Arc;
+use lazy_static::*;
+use task::{TaskControlBlock, TaskStatus};
+
+pub use context::TaskContext;
+pub use manager::{add_task, fetch_task};
+pub use pid::{pid_alloc, KernelStack, PidHandle};
+pub use processor::{
+    add_current_syscall_cnt, add_maparea, current_task, current_trap_cx, current_user_token,
+    get_current_running_time, get_current_syscalls_cnt, is_map_vpn_current, remove_mem, run_tasks,
+    schedule, take_current_task,
+};
+
+use crate::loader::get_app_data_by_name;
+
+lazy_static! {
+    /// The init process, every orphaned task is reparented to it
+    pub static ref INITPROC: Arc<TaskControlBlock> = Arc::new(TaskControlBlock::new(
+        get_app_data_by_name("initproc").unwrap()
+    ));
+}
+
+/// Add `INITPROC` to the ready queue; called once during kernel init
+pub fn add_initproc() {
+    add_task(INITPROC.clone());
+}
+
+/// Suspend the current task and hand control to whichever task the
+/// scheduler picks next, putting the suspended task back on the ready queue
+/// first so it can be picked again later
+pub fn suspend_current_and_run_next() {
+    let task = take_current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    let task_cx_ptr = &mut task_inner.task_cx as *mut TaskContext;
+    task_inner.task_status = TaskStatus::Ready;
+    drop(task_inner);
+    add_task(task);
+    schedule(task_cx_ptr);
+}
+
+/// Mark the current task a zombie, record its exit code, reparent its
+/// children to `INITPROC` and switch to the next task; never returns
+pub fn exit_current_and_run_next(exit_code: i32) {
+    let task = take_current_task().unwrap();
+
+    let mut inner = task.inner_exclusive_access();
+    inner.task_status = TaskStatus::Zombie;
+    inner.exit_code = exit_code;
+
+    // move the exiting task's children under INITPROC
+    {
+        let mut initproc_inner = INITPROC.inner_exclusive_access();
+        for child in inner.children.iter() {
+            child.inner_exclusive_access().parent = Some(Arc::downgrade(&INITPROC));
+            initproc_inner.children.push(child.clone());
+        }
+    }
+    inner.children.clear();
+    drop(inner);
+    drop(task);
+    let mut _unused = TaskContext::zero_init();
+    schedule(&mut _unused as *mut _);
+}
+
+/// Change the current task's program break by `size` bytes, returning the
+/// break's value before the change, or `None` if it would under/overflow
+pub fn change_program_brk(_size: i32) -> Option<usize> {
+    // TODO
+    None
+}
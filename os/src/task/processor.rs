@@ -7,7 +7,7 @@
 use super::__switch;
 use super::{fetch_task, TaskStatus};
 use super::{TaskContext, TaskControlBlock};
-use crate::mm::{VirtPageNum, is_map_vpn, MapPermission, VirtAddr, translated_refmut};
+use crate::mm::{VirtPageNum, is_map_vpn, flush_tlb_range, MapPermission, VirtAddr};
 use crate::sync::UPSafeCell;
 use crate::syscall::CH5_SYSCALL_CNT;
 use crate::timer::get_time_us;
@@ -142,23 +142,26 @@ pub fn add_maparea(start_va: VirtAddr, end_va: VirtAddr, perm: usize) {
     let curr_task = current_task().unwrap();
     let mut task_inner = curr_task.inner_exclusive_access();
     task_inner.memory_set.insert_framed_area(start_va, end_va, permission);
+    drop(task_inner);
+    flush_tlb_range(start_va, end_va.ceil());
 }
 
-/// 删除当前进程中的一段内存
-///     当前写法存在问题，只有当要删除的这段内存恰好和之前分配的某一段MapArea匹配时才会删除
-pub fn remove_mem(start_va: VirtAddr, _end_va: VirtAddr) -> isize {
+/// 删除当前进程中 `[start_va, end_va)` 这段虚拟地址范围对应的内存
+/// `memory_set::remove_area` 会按该范围对已有的 `MapArea` 做裁剪：
+/// 完全覆盖的区域整个删掉，只覆盖一端的区域收缩边界，覆盖中间的区域则拆成两段，
+/// 不再要求这段范围恰好等于某一次 `mmap` 分配的区域
+pub fn remove_mem(start_va: VirtAddr, end_va: VirtAddr) -> isize {
     let curr_task = current_task().unwrap();
     let mut task_inner = curr_task.inner_exclusive_access();
-    task_inner.memory_set.remove_area(start_va, end_va)
-}
-
-/// 将当前进程的虚拟地址转换为物理地址
-pub fn curr_translate_refmut<T>(ptr: *mut T) -> &'static mut T {
-    let token = current_user_token();
-    translated_refmut(token, ptr)
+    let ret = task_inner.memory_set.remove_area(start_va, end_va);
+    drop(task_inner);
+    if ret == 0 {
+        flush_tlb_range(start_va, end_va.ceil());
+    }
+    ret
 }
 
-/// 获取当前进程运行时间 
+/// 获取当前进程运行时间
 pub fn get_current_running_time() -> usize {
     let curr_task = current_task().unwrap();
     let task_inner = curr_task.inner_exclusive_access();
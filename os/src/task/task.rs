@@ -1,19 +1,200 @@
-//! Types related to task management
+//! Types related to task management & Process Control Block (PCB)
 
 use super::TaskContext;
-use crate::config::CH2_SYSCALL_NUM;
+use super::{pid_alloc, KernelStack, PidHandle};
+use crate::config::TRAP_CONTEXT;
+use crate::mm::{MemorySet, PhysPageNum, VirtAddr, KERNEL_SPACE};
+use crate::sync::UPSafeCell;
+use crate::syscall::CH5_SYSCALL_CNT;
+use crate::trap::{trap_handler, TrapContext};
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::cell::RefMut;
 
-/// The task control block (TCB) of a task.
-#[derive(Copy, Clone)]
+/// The task control block (TCB) of a task, now doubling as a process control
+/// block: `pid`/`kernel_stack` are fixed for the task's whole lifetime and
+/// sit outside the lock, while everything that's mutated while the task
+/// runs lives in [`TaskControlBlockInner`] behind `UPSafeCell`, the same
+/// discipline `Processor` uses for its own state.
 pub struct TaskControlBlock {
-    /// The task status in it's lifecycle
+    /// process identifier, stable for the whole lifetime of the task
+    pub pid: PidHandle,
+    /// kernel stack backing this task's traps and syscalls
+    pub kernel_stack: KernelStack,
+    inner: UPSafeCell<TaskControlBlockInner>,
+}
+
+/// Mutable part of a [`TaskControlBlock`]
+pub struct TaskControlBlockInner {
+    /// The task status in it's life cycle
     pub task_status: TaskStatus,
     /// The task context
     pub task_cx: TaskContext,
+    /// physical page number of this task's trap context frame
+    pub trap_cx_ppn: PhysPageNum,
+    /// this task's address space
+    pub memory_set: MemorySet,
+    /// where `sbrk` should grow the data segment from
+    pub base_size: usize,
     /// 该任务的开始时间
     pub start_time: isize,
     /// 该任务所调用的不同syscall的次数
-    pub syscalls_cnt: [u32; CH2_SYSCALL_NUM],
+    pub tong_syscalls_cnt: [usize; CH5_SYSCALL_CNT],
+    /// stride-scheduling accumulator, bumped by `BIG_STRIDER / prio_level` on every run
+    pub stride: u8,
+    /// stride-scheduling priority; must stay `>= MIN_PRIO_LEVEL` so stride comparisons
+    /// stay correct across `u8` wraparound
+    pub prio_level: u8,
+    /// parent task (`None` only for `initproc`)
+    pub parent: Option<Weak<TaskControlBlock>>,
+    /// live children of this task
+    pub children: Vec<Arc<TaskControlBlock>>,
+    /// exit code, populated once the task becomes a zombie
+    pub exit_code: i32,
+}
+
+impl TaskControlBlockInner {
+    /// Mutable reference to this task's trap context
+    pub fn get_trap_cx(&self) -> &'static mut TrapContext {
+        self.trap_cx_ppn.get_mut()
+    }
+
+    /// Token (satp value) identifying this task's page table
+    pub fn get_user_token(&self) -> usize {
+        self.memory_set.token()
+    }
+
+    fn get_status(&self) -> TaskStatus {
+        self.task_status
+    }
+
+    /// Whether this task has exited and is waiting on its parent to `waitpid` it
+    pub fn is_zombie(&self) -> bool {
+        self.get_status() == TaskStatus::Zombie
+    }
+}
+
+impl TaskControlBlock {
+    /// Borrow the mutable inner state
+    pub fn inner_exclusive_access(&self) -> RefMut<'_, TaskControlBlockInner> {
+        self.inner.exclusive_access()
+    }
+
+    /// Token (satp value) identifying this task's page table
+    pub fn get_user_token(&self) -> usize {
+        self.inner_exclusive_access().get_user_token()
+    }
+
+    /// This task's pid
+    pub fn getpid(&self) -> usize {
+        self.pid.0
+    }
+
+    /// Build a fresh task control block from an ELF image, with no parent.
+    /// Used for `initproc`; `exec` loads its image through the same
+    /// `MemorySet::from_elf` without building a new control block.
+    pub fn new(elf_data: &[u8]) -> Self {
+        let (memory_set, user_sp, entry_point) = MemorySet::from_elf(elf_data);
+        let trap_cx_ppn = memory_set
+            .translate(VirtAddr::from(TRAP_CONTEXT).into())
+            .unwrap()
+            .ppn();
+        let pid_handle = pid_alloc();
+        let kernel_stack = KernelStack::new(&pid_handle);
+        let kernel_stack_top = kernel_stack.get_top();
+        let task_control_block = Self {
+            pid: pid_handle,
+            kernel_stack,
+            inner: unsafe {
+                UPSafeCell::new(TaskControlBlockInner {
+                    task_status: TaskStatus::Ready,
+                    task_cx: TaskContext::goto_trap_return(kernel_stack_top),
+                    trap_cx_ppn,
+                    memory_set,
+                    base_size: user_sp,
+                    start_time: -1,
+                    tong_syscalls_cnt: [0; CH5_SYSCALL_CNT],
+                    stride: 0,
+                    prio_level: 16,
+                    parent: None,
+                    children: Vec::new(),
+                    exit_code: 0,
+                })
+            },
+        };
+        let trap_cx = task_control_block.inner_exclusive_access().get_trap_cx();
+        *trap_cx = TrapContext::app_init_context(
+            entry_point,
+            user_sp,
+            KERNEL_SPACE.exclusive_access().token(),
+            kernel_stack_top,
+            trap_handler as usize,
+        );
+        task_control_block
+    }
+
+    /// `fork`: clone this task's address space and trap context into a new
+    /// child task. The child's trap context is a copy of the parent's as of
+    /// its `ecall`, so `a0` holds no return value yet; the caller
+    /// (`sys_fork`) is responsible for zeroing it so the child observes `0`.
+    pub fn fork(self: &Arc<Self>) -> Arc<Self> {
+        let mut parent_inner = self.inner_exclusive_access();
+        let memory_set = MemorySet::from_existed_user(&parent_inner.memory_set);
+        let trap_cx_ppn = memory_set
+            .translate(VirtAddr::from(TRAP_CONTEXT).into())
+            .unwrap()
+            .ppn();
+        let pid_handle = pid_alloc();
+        let kernel_stack = KernelStack::new(&pid_handle);
+        let kernel_stack_top = kernel_stack.get_top();
+        let task_control_block = Arc::new(Self {
+            pid: pid_handle,
+            kernel_stack,
+            inner: unsafe {
+                UPSafeCell::new(TaskControlBlockInner {
+                    task_status: TaskStatus::Ready,
+                    task_cx: TaskContext::goto_trap_return(kernel_stack_top),
+                    trap_cx_ppn,
+                    memory_set,
+                    base_size: parent_inner.base_size,
+                    start_time: -1,
+                    tong_syscalls_cnt: [0; CH5_SYSCALL_CNT],
+                    stride: 0,
+                    prio_level: parent_inner.prio_level,
+                    parent: Some(Arc::downgrade(self)),
+                    children: Vec::new(),
+                    exit_code: 0,
+                })
+            },
+        });
+        parent_inner.children.push(Arc::clone(&task_control_block));
+        let trap_cx = task_control_block.inner_exclusive_access().get_trap_cx();
+        trap_cx.kernel_sp = kernel_stack_top;
+        task_control_block
+    }
+
+    /// `exec`: replace this task's address space with a fresh ELF image,
+    /// keeping its pid, kernel stack and parent/children links
+    pub fn exec(&self, elf_data: &[u8]) {
+        let (memory_set, user_sp, entry_point) = MemorySet::from_elf(elf_data);
+        let trap_cx_ppn = memory_set
+            .translate(VirtAddr::from(TRAP_CONTEXT).into())
+            .unwrap()
+            .ppn();
+        let mut inner = self.inner_exclusive_access();
+        inner.memory_set = memory_set;
+        inner.trap_cx_ppn = trap_cx_ppn;
+        inner.base_size = user_sp;
+        let kernel_stack_top = self.kernel_stack.get_top();
+        let trap_cx = inner.get_trap_cx();
+        *trap_cx = TrapContext::app_init_context(
+            entry_point,
+            user_sp,
+            KERNEL_SPACE.exclusive_access().token(),
+            kernel_stack_top,
+            trap_handler as usize,
+        );
+    }
 }
 
 /// The status of a task
@@ -25,6 +206,6 @@ pub enum TaskStatus {
     Ready,
     /// running
     Running,
-    /// exited
-    Exited,
+    /// exited, waiting for its parent to collect its exit code via `waitpid`
+    Zombie,
 }